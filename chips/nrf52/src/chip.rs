@@ -4,10 +4,14 @@ use crate::deferred_call_tasks::DeferredCallTask;
 use crate::i2c;
 use crate::ieee802154_radio;
 use crate::nvmc;
+use crate::pdm;
 use crate::power;
+use crate::qspi;
 use crate::spi;
 use crate::uart;
+use core::cell::Cell;
 use cortexm4::{self, nvic};
+use kernel::common::cells::OptionalCell;
 use kernel::common::deferred_call;
 use kernel::debug;
 use nrf5x::peripheral_interrupts;
@@ -18,6 +22,229 @@ pub trait InterruptServiceTrait {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool;
 }
 
+/// Highest NVIC line this table can hold. The nRF52's vector table has well
+/// under 64 external interrupt lines; pick a round number with headroom so
+/// boards can register peripherals (e.g. a TIMER driving a software
+/// feature) without bumping this again.
+const NUM_INTERRUPTS: usize = 64;
+
+/// Something that can service one NVIC line, registered at runtime rather
+/// than baked into a `match`.
+///
+/// When a line is shared by more than one peripheral (e.g. `SPI0`/`TWI0`),
+/// the registered handler is expected to do its own `is_enabled()`
+/// disambiguation, exactly as the old hardcoded match arms did.
+pub trait InterruptHandler {
+    unsafe fn handle_interrupt(&self);
+}
+
+#[derive(Copy, Clone)]
+struct HandlerEntry {
+    handler: &'static dyn InterruptHandler,
+}
+
+/// A fixed-size NVIC number -> handler table, filled in by
+/// [`HandlerRegistry::register_handler`] instead of a hardcoded `match`.
+/// This lets out-of-tree peripheral drivers service their own interrupt
+/// without patching this file.
+struct HandlerRegistry {
+    handlers: Cell<[Option<HandlerEntry>; NUM_INTERRUPTS]>,
+}
+
+impl HandlerRegistry {
+    const fn new() -> HandlerRegistry {
+        HandlerRegistry {
+            handlers: Cell::new([None; NUM_INTERRUPTS]),
+        }
+    }
+
+    /// Bind `handler` to service NVIC line `irq`. Boards call this at init
+    /// for every peripheral they want serviced; lines left unregistered
+    /// fall through to `false` in `service_interrupt`.
+    ///
+    /// Panics if `irq` is outside the range this table was sized for,
+    /// mirroring the bounds check `service_interrupt` does on the read
+    /// side.
+    fn register_handler(&self, irq: u32, handler: &'static dyn InterruptHandler) {
+        let idx = irq as usize;
+        assert!(
+            idx < NUM_INTERRUPTS,
+            "register_handler: irq {} is out of range (table holds {} lines)",
+            irq,
+            NUM_INTERRUPTS
+        );
+        let mut handlers = self.handlers.get();
+        handlers[idx] = Some(HandlerEntry { handler });
+        self.handlers.set(handlers);
+    }
+
+    unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
+        let idx = interrupt as usize;
+        if idx >= NUM_INTERRUPTS {
+            return false;
+        }
+        match self.handlers.get()[idx] {
+            Some(entry) => {
+                entry.handler.handle_interrupt();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Wraps the IEEE 802.15.4 and BLE radios, which share a single NVIC line
+/// and can never both be enabled at once.
+struct RadioHandler;
+
+impl InterruptHandler for RadioHandler {
+    unsafe fn handle_interrupt(&self) {
+        match (
+            ieee802154_radio::RADIO.is_enabled(),
+            ble_radio::RADIO.is_enabled(),
+        ) {
+            (false, false) => (),
+            (true, false) => ieee802154_radio::RADIO.handle_interrupt(),
+            (false, true) => ble_radio::RADIO.handle_interrupt(),
+            (true, true) => {
+                debug!("nRF 802.15.4 and BLE radios cannot be simultaneously enabled!")
+            }
+        }
+    }
+}
+
+/// Wraps a SPIM/TWIM pair that share a single NVIC line.
+struct SpiTwiHandler {
+    spi: &'static spi::SPIM,
+    i2c: &'static i2c::TWIM,
+}
+
+impl InterruptHandler for SpiTwiHandler {
+    unsafe fn handle_interrupt(&self) {
+        match (self.spi.is_enabled(), self.i2c.is_enabled()) {
+            (false, false) => (),
+            (true, false) => self.spi.handle_interrupt(),
+            (false, true) => self.i2c.handle_interrupt(),
+            (true, true) => debug_assert!(
+                false,
+                "SPIM and TWIM sharing this line cannot be \
+                 enabled at the same time."
+            ),
+        }
+    }
+}
+
+static SPI0_TWI0_HANDLER: SpiTwiHandler = SpiTwiHandler {
+    spi: &spi::SPIM0,
+    i2c: &i2c::TWIM0,
+};
+
+static SPI1_TWI1_HANDLER: SpiTwiHandler = SpiTwiHandler {
+    spi: &spi::SPIM1,
+    i2c: &i2c::TWIM1,
+};
+
+static RADIO_HANDLER: RadioHandler = RadioHandler;
+
+/// Thin adapters giving the pre-existing, single-owner peripherals an
+/// `InterruptHandler` impl without having to touch their (out-of-crate)
+/// definitions. Each just forwards to the inherent `handle_interrupt()`
+/// these statics already have.
+struct AesEcbHandler;
+impl InterruptHandler for AesEcbHandler {
+    unsafe fn handle_interrupt(&self) {
+        nrf5x::aes::AESECB.handle_interrupt();
+    }
+}
+static AES_ECB_HANDLER: AesEcbHandler = AesEcbHandler;
+
+struct PowerHandler;
+impl InterruptHandler for PowerHandler {
+    unsafe fn handle_interrupt(&self) {
+        power::POWER.handle_interrupt();
+    }
+}
+static POWER_HANDLER: PowerHandler = PowerHandler;
+
+struct TrngHandler;
+impl InterruptHandler for TrngHandler {
+    unsafe fn handle_interrupt(&self) {
+        nrf5x::trng::TRNG.handle_interrupt();
+    }
+}
+static TRNG_HANDLER: TrngHandler = TrngHandler;
+
+struct RtcHandler;
+impl InterruptHandler for RtcHandler {
+    unsafe fn handle_interrupt(&self) {
+        nrf5x::rtc::RTC.handle_interrupt();
+    }
+}
+static RTC_HANDLER: RtcHandler = RtcHandler;
+
+struct TempHandler;
+impl InterruptHandler for TempHandler {
+    unsafe fn handle_interrupt(&self) {
+        nrf5x::temperature::TEMP.handle_interrupt();
+    }
+}
+static TEMP_HANDLER: TempHandler = TempHandler;
+
+struct Timer0Handler;
+impl InterruptHandler for Timer0Handler {
+    unsafe fn handle_interrupt(&self) {
+        nrf5x::timer::TIMER0.handle_interrupt();
+    }
+}
+static TIMER0_HANDLER: Timer0Handler = Timer0Handler;
+
+struct Timer1Handler;
+impl InterruptHandler for Timer1Handler {
+    unsafe fn handle_interrupt(&self) {
+        nrf5x::timer::ALARM1.handle_interrupt();
+    }
+}
+static TIMER1_HANDLER: Timer1Handler = Timer1Handler;
+
+struct Timer2Handler;
+impl InterruptHandler for Timer2Handler {
+    unsafe fn handle_interrupt(&self) {
+        nrf5x::timer::TIMER2.handle_interrupt();
+    }
+}
+static TIMER2_HANDLER: Timer2Handler = Timer2Handler;
+
+struct Spim2Handler;
+impl InterruptHandler for Spim2Handler {
+    unsafe fn handle_interrupt(&self) {
+        spi::SPIM2.handle_interrupt();
+    }
+}
+static SPIM2_HANDLER: Spim2Handler = Spim2Handler;
+
+struct AdcHandler;
+impl InterruptHandler for AdcHandler {
+    unsafe fn handle_interrupt(&self) {
+        adc::ADC.handle_interrupt();
+    }
+}
+static ADC_HANDLER: AdcHandler = AdcHandler;
+
+/// Wraps the GPIO port so it can be registered like any other handler even
+/// though, unlike the statics above, which `Port` to service is a
+/// per-board choice passed into `InterruptService::new`.
+struct GpioHandler {
+    port: OptionalCell<&'static nrf5x::gpio::Port>,
+}
+impl InterruptHandler for GpioHandler {
+    unsafe fn handle_interrupt(&self) {
+        self.port.map(|port| port.handle_interrupt());
+    }
+}
+static GPIO_HANDLER: GpioHandler = GpioHandler {
+    port: OptionalCell::empty(),
+};
+
 pub struct NRF52 {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
@@ -39,74 +266,49 @@ impl NRF52 {
 }
 
 pub struct InterruptService {
-    gpio_port: &'static nrf5x::gpio::Port,
+    registry: HandlerRegistry,
 }
 
 impl InterruptService {
     pub unsafe fn new(gpio_port: &'static nrf5x::gpio::Port) -> InterruptService {
-        InterruptService { gpio_port }
+        let service = InterruptService {
+            registry: HandlerRegistry::new(),
+        };
+        GPIO_HANDLER.port.set(gpio_port);
+        service.register_handler(peripheral_interrupts::ECB, &AES_ECB_HANDLER);
+        service.register_handler(peripheral_interrupts::GPIOTE, &GPIO_HANDLER);
+        service.register_handler(peripheral_interrupts::POWER_CLOCK, &POWER_HANDLER);
+        service.register_handler(peripheral_interrupts::RADIO, &RADIO_HANDLER);
+        service.register_handler(peripheral_interrupts::RNG, &TRNG_HANDLER);
+        service.register_handler(peripheral_interrupts::RTC1, &RTC_HANDLER);
+        service.register_handler(peripheral_interrupts::TEMP, &TEMP_HANDLER);
+        service.register_handler(peripheral_interrupts::TIMER0, &TIMER0_HANDLER);
+        service.register_handler(peripheral_interrupts::TIMER1, &TIMER1_HANDLER);
+        service.register_handler(peripheral_interrupts::TIMER2, &TIMER2_HANDLER);
+        service.register_handler(peripheral_interrupts::UART0, &uart::UARTE0);
+        service.register_handler(peripheral_interrupts::SPI0_TWI0, &SPI0_TWI0_HANDLER);
+        service.register_handler(peripheral_interrupts::SPI1_TWI1, &SPI1_TWI1_HANDLER);
+        service.register_handler(peripheral_interrupts::SPIM2_SPIS2_SPI2, &SPIM2_HANDLER);
+        service.register_handler(peripheral_interrupts::ADC, &ADC_HANDLER);
+        service.register_handler(peripheral_interrupts::PDM, &pdm::PDM);
+        // TIMER3 is dedicated to `uart::Uarte`'s receive_automatic idle-line
+        // detection; see uart.rs for how it uses the PPI channels.
+        service.register_handler(peripheral_interrupts::TIMER3, &uart::UART0_RX_TIMEOUT_HANDLER);
+        service.register_handler(peripheral_interrupts::QSPI, &qspi::QSPI);
+        service
+    }
+
+    /// Bind `handler` to service NVIC line `irq`. Chips/boards call this
+    /// for any peripheral this crate doesn't already wire up above, e.g. a
+    /// capsule-owned TIMER or an out-of-tree DMA peripheral.
+    pub fn register_handler(&self, irq: u32, handler: &'static dyn InterruptHandler) {
+        self.registry.register_handler(irq, handler);
     }
 }
 
 impl InterruptServiceTrait for InterruptService {
     unsafe fn service_interrupt(&self, interrupt: u32) -> bool {
-        match interrupt {
-            peripheral_interrupts::ECB => nrf5x::aes::AESECB.handle_interrupt(),
-            peripheral_interrupts::GPIOTE => self.gpio_port.handle_interrupt(),
-            peripheral_interrupts::POWER_CLOCK => power::POWER.handle_interrupt(),
-            peripheral_interrupts::RADIO => {
-                match (
-                    ieee802154_radio::RADIO.is_enabled(),
-                    ble_radio::RADIO.is_enabled(),
-                ) {
-                    (false, false) => (),
-                    (true, false) => ieee802154_radio::RADIO.handle_interrupt(),
-                    (false, true) => ble_radio::RADIO.handle_interrupt(),
-                    (true, true) => {
-                        debug!("nRF 802.15.4 and BLE radios cannot be simultaneously enabled!")
-                    }
-                }
-            }
-            peripheral_interrupts::RNG => nrf5x::trng::TRNG.handle_interrupt(),
-            peripheral_interrupts::RTC1 => nrf5x::rtc::RTC.handle_interrupt(),
-            peripheral_interrupts::TEMP => nrf5x::temperature::TEMP.handle_interrupt(),
-            peripheral_interrupts::TIMER0 => nrf5x::timer::TIMER0.handle_interrupt(),
-            peripheral_interrupts::TIMER1 => nrf5x::timer::ALARM1.handle_interrupt(),
-            peripheral_interrupts::TIMER2 => nrf5x::timer::TIMER2.handle_interrupt(),
-            peripheral_interrupts::UART0 => uart::UARTE0.handle_interrupt(),
-            peripheral_interrupts::SPI0_TWI0 => {
-                // SPI0 and TWI0 share interrupts.
-                // Dispatch the correct handler.
-                match (spi::SPIM0.is_enabled(), i2c::TWIM0.is_enabled()) {
-                    (false, false) => (),
-                    (true, false) => spi::SPIM0.handle_interrupt(),
-                    (false, true) => i2c::TWIM0.handle_interrupt(),
-                    (true, true) => debug_assert!(
-                        false,
-                        "SPIM0 and TWIM0 cannot be \
-                         enabled at the same time."
-                    ),
-                }
-            }
-            peripheral_interrupts::SPI1_TWI1 => {
-                // SPI1 and TWI1 share interrupts.
-                // Dispatch the correct handler.
-                match (spi::SPIM1.is_enabled(), i2c::TWIM1.is_enabled()) {
-                    (false, false) => (),
-                    (true, false) => spi::SPIM1.handle_interrupt(),
-                    (false, true) => i2c::TWIM1.handle_interrupt(),
-                    (true, true) => debug_assert!(
-                        false,
-                        "SPIM1 and TWIM1 cannot be \
-                         enabled at the same time."
-                    ),
-                }
-            }
-            peripheral_interrupts::SPIM2_SPIS2_SPI2 => spi::SPIM2.handle_interrupt(),
-            peripheral_interrupts::ADC => adc::ADC.handle_interrupt(),
-            _ => return false,
-        }
-        true
+        self.registry.service_interrupt(interrupt)
     }
 }
 
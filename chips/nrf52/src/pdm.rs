@@ -0,0 +1,232 @@
+//! PDM driver, nRF52840 and nRF52833
+//!
+//! Pulse Density Modulation (PDM) interface for sampling from MEMS
+//! microphones over a clock/data pair, with EasyDMA streaming captured PCM
+//! samples directly into a double buffer supplied by the client.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, register_structs, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::ReturnCode;
+
+const PDM_BASE: StaticRef<PdmRegisters> =
+    unsafe { StaticRef::new(0x40056000 as *const PdmRegisters) };
+
+register_structs! {
+    PdmRegisters {
+        (0x000 => task_start: WriteOnly<u32, Task::Register>),
+        (0x004 => task_stop: WriteOnly<u32, Task::Register>),
+        (0x008 => _reserved0),
+        (0x010 => event_started: ReadWrite<u32, Event::Register>),
+        (0x014 => event_stopped: ReadWrite<u32, Event::Register>),
+        (0x018 => _reserved1),
+        (0x0b0 => event_end: ReadWrite<u32, Event::Register>),
+        (0x0b4 => _reserved2),
+        (0x100 => inten: ReadWrite<u32, Interrupt::Register>),
+        (0x104 => intenset: ReadWrite<u32, Interrupt::Register>),
+        (0x108 => intenclr: ReadWrite<u32, Interrupt::Register>),
+        (0x10c => _reserved3),
+        (0x500 => enable: ReadWrite<u32, Enable::Register>),
+        (0x504 => pdmclkctrl: ReadWrite<u32, ClockControl::Register>),
+        (0x508 => mode: ReadWrite<u32, Mode::Register>),
+        (0x50c => _reserved4),
+        (0x518 => psel_clk: ReadWrite<u32, Psel::Register>),
+        (0x51c => psel_din: ReadWrite<u32, Psel::Register>),
+        (0x520 => _reserved5),
+        (0x540 => gainl: ReadWrite<u32, Gain::Register>),
+        (0x544 => gainr: ReadWrite<u32, Gain::Register>),
+        (0x548 => _reserved6),
+        (0x560 => sample_ptr: ReadWrite<u32, SamplePtr::Register>),
+        (0x564 => sample_maxcnt: ReadWrite<u32, SampleMaxCnt::Register>),
+        (0x568 => @END),
+    }
+}
+
+register_bitfields![u32,
+    Task [
+        TASK 1
+    ],
+    Event [
+        EVENT 1
+    ],
+    Interrupt [
+        STARTED 0,
+        STOPPED 1,
+        END 6
+    ],
+    Enable [
+        ENABLE OFFSET(0) NUMBITS(1) [
+            DISABLED = 0,
+            ENABLED = 1
+        ]
+    ],
+    ClockControl [
+        FREQ OFFSET(0) NUMBITS(32)
+    ],
+    Mode [
+        OPERATION OFFSET(0) NUMBITS(1) [
+            STEREO = 0,
+            MONO = 1
+        ],
+        EDGE OFFSET(1) NUMBITS(1) [
+            LEFTFALLING = 0,
+            LEFTRISING = 1
+        ]
+    ],
+    Psel [
+        PIN OFFSET(0) NUMBITS(5),
+        CONNECT OFFSET(31) NUMBITS(1) [
+            CONNECTED = 0,
+            DISCONNECTED = 1
+        ]
+    ],
+    Gain [
+        GAIN OFFSET(0) NUMBITS(7)
+    ],
+    SamplePtr [
+        SAMPLEPTR OFFSET(0) NUMBITS(32)
+    ],
+    SampleMaxCnt [
+        BUFFSIZE OFFSET(0) NUMBITS(15)
+    ]
+];
+
+/// Number of microphone channels the PDM peripheral can capture at once.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Channels {
+    Mono,
+    Stereo,
+}
+
+/// Client for a completed capture buffer.
+pub trait PdmClient {
+    /// `buf` holds `samples` captured 16-bit PCM words; ownership returns to
+    /// the client until it is handed back with `provide_buffer`.
+    fn sample_done(&self, buf: &'static mut [u16], samples: usize);
+}
+
+pub struct Pdm<'a> {
+    registers: StaticRef<PdmRegisters>,
+    client: OptionalCell<&'a dyn PdmClient>,
+    buffer: TakeCell<'static, [u16]>,
+    next_buffer: TakeCell<'static, [u16]>,
+    enabled: Cell<bool>,
+}
+
+impl<'a> Pdm<'a> {
+    pub const fn new() -> Pdm<'a> {
+        Pdm {
+            registers: PDM_BASE,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::empty(),
+            next_buffer: TakeCell::empty(),
+            enabled: Cell::new(false),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn PdmClient) {
+        self.client.set(client);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// Configure the PDM clock divider and channel count. `lclk_freq` is the
+    /// raw `PDMCLKCTRL.FREQ` value corresponding to the desired sample rate;
+    /// boards pick this from the datasheet's clock table for their target
+    /// rate (e.g. 16 kHz mono). `MODE.OPERATION` is what actually switches
+    /// EasyDMA between one sample per cycle (mono) and interleaved L/R
+    /// (stereo); gain is independent of channel count and left to `set_gain`.
+    pub fn configure(&self, lclk_freq: u32, channels: Channels) {
+        let regs = &*self.registers;
+        regs.pdmclkctrl.write(ClockControl::FREQ.val(lclk_freq));
+        let operation = match channels {
+            Channels::Mono => Mode::OPERATION::MONO,
+            Channels::Stereo => Mode::OPERATION::STEREO,
+        };
+        regs.mode.write(operation);
+    }
+
+    /// Set the left/right microphone gain. Valid range is the datasheet's
+    /// `GAIN` encoding (roughly -20dB to +20dB).
+    pub fn set_gain(&self, left: u8, right: u8) {
+        let regs = &*self.registers;
+        regs.gainl.write(Gain::GAIN.val(left as u32));
+        regs.gainr.write(Gain::GAIN.val(right as u32));
+    }
+
+    pub fn set_pins(&self, clk_pin: u32, din_pin: u32) {
+        let regs = &*self.registers;
+        regs.psel_clk
+            .write(Psel::PIN.val(clk_pin) + Psel::CONNECT::CONNECTED);
+        regs.psel_din
+            .write(Psel::PIN.val(din_pin) + Psel::CONNECT::CONNECTED);
+    }
+
+    /// Begin sampling into `buf`, capturing up to `buf.len()` 16-bit PCM
+    /// words via EasyDMA. Returns the buffer back with `EBUSY` if sampling
+    /// is already running.
+    pub fn start_sampling(&self, buf: &'static mut [u16]) -> Result<(), (ReturnCode, &'static mut [u16])> {
+        if self.enabled.get() {
+            return Err((ReturnCode::EBUSY, buf));
+        }
+        let regs = &*self.registers;
+        regs.sample_ptr.set(buf.as_ptr() as u32);
+        regs.sample_maxcnt.write(SampleMaxCnt::BUFFSIZE.val(buf.len() as u32));
+        self.buffer.replace(buf);
+        regs.enable.write(Enable::ENABLE::ENABLED);
+        regs.intenset.write(Interrupt::END::SET);
+        regs.task_start.write(Task::TASK::SET);
+        self.enabled.set(true);
+        Ok(())
+    }
+
+    /// Queue a second buffer so sampling continues without a gap once the
+    /// first buffer fills, following the same double-buffer pattern as the
+    /// other EasyDMA peripherals in this crate.
+    pub fn provide_buffer(&self, buf: &'static mut [u16]) {
+        let regs = &*self.registers;
+        regs.sample_ptr.set(buf.as_ptr() as u32);
+        regs.sample_maxcnt.write(SampleMaxCnt::BUFFSIZE.val(buf.len() as u32));
+        self.next_buffer.replace(buf);
+    }
+
+    pub fn stop_sampling(&self) {
+        let regs = &*self.registers;
+        regs.task_stop.write(Task::TASK::SET);
+        regs.enable.write(Enable::ENABLE::DISABLED);
+        self.enabled.set(false);
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+        if regs.event_end.is_set(Event::EVENT) {
+            regs.event_end.write(Event::EVENT::CLEAR);
+            let samples = regs.sample_maxcnt.read(SampleMaxCnt::BUFFSIZE) as usize;
+            let completed = self.buffer.take();
+            if let Some(next) = self.next_buffer.take() {
+                regs.sample_ptr.set(next.as_ptr() as u32);
+                self.buffer.replace(next);
+            }
+            completed.map(|buf| {
+                self.client.map(|client| client.sample_done(buf, samples));
+            });
+        }
+        if regs.event_started.is_set(Event::EVENT) {
+            regs.event_started.write(Event::EVENT::CLEAR);
+        }
+        if regs.event_stopped.is_set(Event::EVENT) {
+            regs.event_stopped.write(Event::EVENT::CLEAR);
+        }
+    }
+}
+
+impl<'a> crate::chip::InterruptHandler for Pdm<'a> {
+    unsafe fn handle_interrupt(&self) {
+        Pdm::handle_interrupt(self)
+    }
+}
+
+pub static PDM: Pdm = Pdm::new();
@@ -0,0 +1,326 @@
+//! QSPI driver, nRF52840.
+//!
+//! Talks to an external SPI-NOR flash over the QSPI peripheral's
+//! memory-mapped/serial interface, in single/dual/quad I/O modes, and
+//! implements `kernel::hil::flash::Flash` (one `PAGE_SIZE` page per
+//! `read_page`/`write_page`/`erase_page` call) so a storage capsule can use
+//! off-chip flash the same way it would the on-chip `nvmc`.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, register_structs, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+use kernel::ReturnCode;
+
+const QSPI_BASE: StaticRef<QspiRegisters> =
+    unsafe { StaticRef::new(0x40029000 as *const QspiRegisters) };
+
+register_structs! {
+    QspiRegisters {
+        (0x000 => task_activate: WriteOnly<u32, Task::Register>),
+        (0x004 => task_readstart: WriteOnly<u32, Task::Register>),
+        (0x008 => task_writestart: WriteOnly<u32, Task::Register>),
+        (0x00c => task_erasestart: WriteOnly<u32, Task::Register>),
+        (0x010 => task_deactivate: WriteOnly<u32, Task::Register>),
+        (0x014 => _reserved0),
+        (0x100 => event_ready: ReadWrite<u32, Event::Register>),
+        (0x104 => _reserved1),
+        (0x200 => inten: ReadWrite<u32, Interrupt::Register>),
+        (0x204 => intenset: ReadWrite<u32, Interrupt::Register>),
+        (0x208 => intenclr: ReadWrite<u32, Interrupt::Register>),
+        (0x20c => _reserved2),
+        (0x500 => enable: ReadWrite<u32, Enable::Register>),
+        (0x504 => read_src: ReadWrite<u32>),
+        (0x508 => read_dst: ReadWrite<u32>),
+        (0x50c => read_cnt: ReadWrite<u32, Count::Register>),
+        (0x510 => write_src: ReadWrite<u32>),
+        (0x514 => write_dst: ReadWrite<u32>),
+        (0x518 => write_cnt: ReadWrite<u32, Count::Register>),
+        (0x51c => erase_ptr: ReadWrite<u32>),
+        (0x520 => erase_len: ReadWrite<u32, EraseLen::Register>),
+        (0x524 => ifconfig0: ReadWrite<u32, IfConfig0::Register>),
+        (0x528 => _reserved3),
+        (0x604 => ifconfig1: ReadWrite<u32, IfConfig1::Register>),
+        (0x608 => @END),
+    }
+}
+
+register_bitfields![u32,
+    Task [
+        TASK 1
+    ],
+    Event [
+        EVENT 1
+    ],
+    Interrupt [
+        READY 0
+    ],
+    Enable [
+        ENABLE OFFSET(0) NUMBITS(1) [
+            DISABLED = 0,
+            ENABLED = 1
+        ]
+    ],
+    Count [
+        COUNT OFFSET(0) NUMBITS(20)
+    ],
+    EraseLen [
+        SIZE OFFSET(0) NUMBITS(2) [
+            ERASE4KB = 0,
+            ERASE32KB = 1,
+            ERASE64KB = 2,
+            ERASEALL = 3
+        ]
+    ],
+    IfConfig0 [
+        READOC OFFSET(0) NUMBITS(3) [
+            FASTREAD = 0,
+            READ2O = 1,
+            READ2IO = 2,
+            READ4O = 3,
+            READ4IO = 4
+        ],
+        WRITEOC OFFSET(3) NUMBITS(3) [
+            PP = 0,
+            PP2O = 1,
+            PP4O = 2,
+            PP4IO = 3
+        ],
+        ADDRMODE OFFSET(6) NUMBITS(1) [
+            BIT24 = 0,
+            BIT32 = 1
+        ],
+        DPMENABLE OFFSET(7) NUMBITS(1)
+    ],
+    IfConfig1 [
+        SCKDELAY OFFSET(0) NUMBITS(8),
+        DUMMY OFFSET(8) NUMBITS(5),
+        DPMEN OFFSET(24) NUMBITS(1),
+        SPIMODE OFFSET(25) NUMBITS(1),
+        SCKFREQ OFFSET(28) NUMBITS(4)
+    ]
+];
+
+/// Serial I/O width used for read/write commands.
+#[derive(Copy, Clone, PartialEq)]
+pub enum IoMode {
+    Single,
+    Dual,
+    Quad,
+}
+
+/// Address width the attached flash expects.
+#[derive(Copy, Clone, PartialEq)]
+pub enum AddressWidth {
+    Bit24,
+    Bit32,
+}
+
+/// QSPI read/write/erase timing and protocol configuration for the
+/// attached SPI-NOR part.
+pub struct QspiConfig {
+    pub read_mode: IoMode,
+    pub write_mode: IoMode,
+    pub address_width: AddressWidth,
+    /// Number of dummy clock cycles the flash needs between the address
+    /// and the returned data on a fast-read command.
+    pub dummy_cycles: u8,
+    pub sck_frequency: u8,
+}
+
+pub enum QspiOp {
+    Read,
+    Write,
+    Erase,
+}
+
+/// Erase granularity of `erase_page`/`QspiOp::Erase`: one 4KB sector.
+pub const PAGE_SIZE: usize = 4096;
+
+/// A page-sized buffer, sized and aligned the way `hil::flash::Flash`
+/// expects, so a storage capsule can hand `Qspi` a page at a time the same
+/// way it would the on-chip `nvmc`.
+pub struct QspiPage(pub [u8; PAGE_SIZE]);
+
+impl Default for QspiPage {
+    fn default() -> QspiPage {
+        QspiPage([0; PAGE_SIZE])
+    }
+}
+
+impl AsMut<[u8]> for QspiPage {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+pub struct Qspi<'a> {
+    registers: StaticRef<QspiRegisters>,
+    client: OptionalCell<&'a dyn hil::flash::Client<Qspi<'a>>>,
+    buffer: TakeCell<'static, QspiPage>,
+    address: Cell<u32>,
+    operation: OptionalCell<QspiOp>,
+}
+
+impl<'a> Qspi<'a> {
+    pub const fn new() -> Qspi<'a> {
+        Qspi {
+            registers: QSPI_BASE,
+            client: OptionalCell::empty(),
+            buffer: TakeCell::empty(),
+            address: Cell::new(0),
+            operation: OptionalCell::empty(),
+        }
+    }
+
+    pub fn configure(&self, config: &QspiConfig) {
+        let regs = &*self.registers;
+
+        let readoc = match config.read_mode {
+            IoMode::Single => IfConfig0::READOC::FASTREAD,
+            IoMode::Dual => IfConfig0::READOC::READ2IO,
+            IoMode::Quad => IfConfig0::READOC::READ4IO,
+        };
+        let writeoc = match config.write_mode {
+            IoMode::Single => IfConfig0::WRITEOC::PP,
+            IoMode::Dual => IfConfig0::WRITEOC::PP2O,
+            IoMode::Quad => IfConfig0::WRITEOC::PP4IO,
+        };
+        let addrmode = match config.address_width {
+            AddressWidth::Bit24 => IfConfig0::ADDRMODE::BIT24,
+            AddressWidth::Bit32 => IfConfig0::ADDRMODE::BIT32,
+        };
+        regs.ifconfig0.write(readoc + writeoc + addrmode);
+        regs.ifconfig1.write(
+            IfConfig1::SCKFREQ.val(config.sck_frequency as u32)
+                + IfConfig1::DUMMY.val(config.dummy_cycles as u32),
+        );
+    }
+
+    pub fn enable(&self) {
+        let regs = &*self.registers;
+        regs.enable.write(Enable::ENABLE::ENABLED);
+        regs.intenset.write(Interrupt::READY::SET);
+        regs.task_activate.write(Task::TASK::SET);
+    }
+
+    pub fn disable(&self) {
+        self.registers.task_deactivate.write(Task::TASK::SET);
+    }
+
+    fn start(
+        &self,
+        op: QspiOp,
+        address: u32,
+        buf: &'static mut QspiPage,
+    ) -> Result<(), (ReturnCode, &'static mut QspiPage)> {
+        if self.operation.is_some() {
+            return Err((ReturnCode::EBUSY, buf));
+        }
+        let regs = &*self.registers;
+        let ptr = buf.0.as_ptr() as u32;
+        let len = buf.0.len() as u32;
+        match op {
+            QspiOp::Read => {
+                regs.read_src.set(address);
+                regs.read_dst.set(ptr);
+                regs.read_cnt.write(Count::COUNT.val(len));
+                regs.task_readstart.write(Task::TASK::SET);
+            }
+            QspiOp::Write => {
+                regs.write_src.set(ptr);
+                regs.write_dst.set(address);
+                regs.write_cnt.write(Count::COUNT.val(len));
+                regs.task_writestart.write(Task::TASK::SET);
+            }
+            QspiOp::Erase => return Err((ReturnCode::EINVAL, buf)),
+        }
+        self.buffer.replace(buf);
+        self.address.set(address);
+        self.operation.set(op);
+        Ok(())
+    }
+
+    /// Erase the 4KB sector at `address`, which must be `PAGE_SIZE`-aligned.
+    /// Erase needs no data buffer, so unlike read/write there's nothing to
+    /// hand back on completion beyond the `erase_complete` callback itself.
+    fn erase(&self, address: u32) -> ReturnCode {
+        if self.operation.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        let regs = &*self.registers;
+        regs.erase_ptr.set(address);
+        regs.erase_len.write(EraseLen::SIZE::ERASE4KB);
+        regs.task_erasestart.write(Task::TASK::SET);
+        self.address.set(address);
+        self.operation.set(QspiOp::Erase);
+        ReturnCode::SUCCESS
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+        if regs.event_ready.is_set(Event::EVENT) {
+            regs.event_ready.write(Event::EVENT::CLEAR);
+            if let Some(op) = self.operation.take() {
+                match op {
+                    QspiOp::Erase => {
+                        self.client
+                            .map(|client| client.erase_complete(hil::flash::Error::CommandComplete));
+                    }
+                    QspiOp::Read | QspiOp::Write => {
+                        self.buffer.take().map(|buf| {
+                            self.client.map(|client| match op {
+                                QspiOp::Read => {
+                                    client.read_complete(buf, hil::flash::Error::CommandComplete)
+                                }
+                                QspiOp::Write => {
+                                    client.write_complete(buf, hil::flash::Error::CommandComplete)
+                                }
+                                QspiOp::Erase => unreachable!(),
+                            });
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> hil::flash::HasClient<'a, Qspi<'a>> for Qspi<'a> {
+    fn set_client(&self, client: &'a dyn hil::flash::Client<Qspi<'a>>) {
+        self.client.set(client);
+    }
+}
+
+impl<'a> hil::flash::Flash for Qspi<'a> {
+    type Page = QspiPage;
+
+    fn read_page(
+        &self,
+        page_number: usize,
+        buf: &'static mut Self::Page,
+    ) -> Result<(), (ReturnCode, &'static mut Self::Page)> {
+        self.start(QspiOp::Read, (page_number * PAGE_SIZE) as u32, buf)
+    }
+
+    fn write_page(
+        &self,
+        page_number: usize,
+        buf: &'static mut Self::Page,
+    ) -> Result<(), (ReturnCode, &'static mut Self::Page)> {
+        self.start(QspiOp::Write, (page_number * PAGE_SIZE) as u32, buf)
+    }
+
+    fn erase_page(&self, page_number: usize) -> ReturnCode {
+        self.erase((page_number * PAGE_SIZE) as u32)
+    }
+}
+
+impl<'a> crate::chip::InterruptHandler for Qspi<'a> {
+    unsafe fn handle_interrupt(&self) {
+        Qspi::handle_interrupt(self)
+    }
+}
+
+pub static QSPI: Qspi = Qspi::new();
@@ -0,0 +1,233 @@
+//! UARTE driver, nRF52.
+//!
+//! This driver only implements `receive_automatic`: a TIMER and two PPI
+//! channels stop reception after the line has gone idle rather than
+//! requiring the caller to know how many bytes are coming. It does not
+//! implement transmit or fixed-length receive; those need their own
+//! length-driven register plumbing and are out of scope here.
+
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::registers::{register_bitfields, register_structs, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::ReturnCode;
+use nrf5x::ppi;
+use nrf5x::pinmux::Pinmux;
+
+const UARTE0_BASE: StaticRef<UarteRegisters> =
+    unsafe { StaticRef::new(0x40002000 as *const UarteRegisters) };
+
+register_structs! {
+    UarteRegisters {
+        (0x000 => task_startrx: WriteOnly<u32, Task::Register>),
+        (0x004 => task_stoprx: WriteOnly<u32, Task::Register>),
+        (0x008 => _reserved0),
+        (0x108 => event_endrx: ReadWrite<u32, Event::Register>),
+        (0x10c => _reserved1),
+        (0x11c => event_rxdrdy: ReadWrite<u32, Event::Register>),
+        (0x120 => _reserved2),
+        (0x200 => inten: ReadWrite<u32, Interrupt::Register>),
+        (0x204 => intenset: ReadWrite<u32, Interrupt::Register>),
+        (0x208 => intenclr: ReadWrite<u32, Interrupt::Register>),
+        (0x20c => _reserved3),
+        (0x500 => enable: ReadWrite<u32, Enable::Register>),
+        (0x504 => _reserved4),
+        (0x514 => psel_rxd: ReadWrite<u32>),
+        (0x518 => _reserved5),
+        (0x524 => baudrate: ReadWrite<u32, Baudrate::Register>),
+        (0x528 => _reserved6),
+        (0x534 => rxd_ptr: ReadWrite<u32>),
+        (0x538 => rxd_maxcnt: ReadWrite<u32, Count::Register>),
+        (0x53c => rxd_amount: ReadWrite<u32, Count::Register>),
+        (0x540 => @END),
+    }
+}
+
+register_bitfields![u32,
+    Task [
+        TASK 1
+    ],
+    Event [
+        EVENT 1
+    ],
+    Interrupt [
+        ENDRX 4,
+        RXDRDY 7
+    ],
+    Enable [
+        ENABLE OFFSET(0) NUMBITS(4) [
+            DISABLED = 0,
+            ENABLED = 8
+        ]
+    ],
+    Baudrate [
+        BAUDRATE OFFSET(0) NUMBITS(32)
+    ],
+    Count [
+        COUNT OFFSET(0) NUMBITS(16)
+    ]
+];
+
+/// Client for the `receive_automatic` idle-line API.
+pub trait ReceiveAutomaticClient {
+    /// `buf` holds `rx_len` bytes received before the line went idle for
+    /// roughly two byte-times.
+    fn received_until_idle(&self, buf: &'static mut [u8], rx_len: usize);
+}
+
+/// TIMER CC value needed to time out after ~2 idle byte periods:
+/// 20 bit-times (start + 8 data + stop, times two bytes) at the UARTE's
+/// 16MHz HFCLK reference.
+fn idle_timeout_ticks(baud: u32) -> u32 {
+    (20 * 16_000_000) / baud
+}
+
+pub struct Uarte<'a> {
+    registers: StaticRef<UarteRegisters>,
+    idle_client: OptionalCell<&'a dyn ReceiveAutomaticClient>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    enabled: Cell<bool>,
+    baud_rate: Cell<u32>,
+    /// TIMER instance and the two PPI channels used for RXDRDY->CLEAR/START
+    /// and COMPARE->STOPRX, supplied by `set_rx_timeout_resources`.
+    rx_timeout_timer: OptionalCell<&'static nrf5x::timer::TimerAlarm<'static>>,
+    rxdrdy_ppi_channel: OptionalCell<ppi::Ppi>,
+    timeout_ppi_channel: OptionalCell<ppi::Ppi>,
+}
+
+impl<'a> Uarte<'a> {
+    pub const fn new() -> Uarte<'a> {
+        Uarte {
+            registers: UARTE0_BASE,
+            idle_client: OptionalCell::empty(),
+            rx_buffer: TakeCell::empty(),
+            enabled: Cell::new(false),
+            baud_rate: Cell::new(115200),
+            rx_timeout_timer: OptionalCell::empty(),
+            rxdrdy_ppi_channel: OptionalCell::empty(),
+            timeout_ppi_channel: OptionalCell::empty(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// Enable the peripheral and mux `rx_pin` to its RXD line. This driver
+    /// is receive-only (see the module docs), so there's no `tx_pin`/
+    /// PSEL.TXD to configure.
+    pub fn init(&self, rx_pin: Pinmux) {
+        self.registers.psel_rxd.set(rx_pin.into());
+        self.registers.enable.write(Enable::ENABLE::ENABLED);
+        self.enabled.set(true);
+    }
+
+    pub fn set_baud_rate(&self, baud_rate: u32, baudrate_reg: u32) {
+        self.baud_rate.set(baud_rate);
+        self.registers.baudrate.write(Baudrate::BAUDRATE.val(baudrate_reg));
+    }
+
+    pub fn set_receive_automatic_client(&self, client: &'a dyn ReceiveAutomaticClient) {
+        self.idle_client.set(client);
+    }
+
+    /// Give the driver the TIMER and PPI channels it should use for idle-line
+    /// detection. Boards wire these in alongside `init()`, since which TIMER
+    /// and channels are free is a board/chip decision, not this driver's.
+    ///
+    /// The driver only enables and disables the two channels around each
+    /// reception; it does not program their event and task endpoints.
+    /// `rxdrdy_channel` is used as the RXDRDY->TIMER CLEAR/START link and
+    /// `timeout_channel` as the TIMER COMPARE->STOPRX link exactly as they
+    /// are configured when passed in.
+    pub fn set_rx_timeout_resources(
+        &self,
+        timer: &'static nrf5x::timer::TimerAlarm<'static>,
+        rxdrdy_channel: ppi::Ppi,
+        timeout_channel: ppi::Ppi,
+    ) {
+        self.rx_timeout_timer.set(timer);
+        self.rxdrdy_ppi_channel.set(rxdrdy_channel);
+        self.timeout_ppi_channel.set(timeout_channel);
+    }
+
+    /// Receive into `buf`, stopping and reporting completion once the line
+    /// has been idle for about two byte-times, rather than waiting for
+    /// `buf` to fill. Returns the buffer back if the timeout resources
+    /// haven't been configured via `set_rx_timeout_resources`.
+    pub fn receive_automatic(
+        &self,
+        buf: &'static mut [u8],
+    ) -> Result<(), (ReturnCode, &'static mut [u8])> {
+        let timer = match self.rx_timeout_timer.extract() {
+            Some(timer) => timer,
+            None => return Err((ReturnCode::EINVAL, buf)),
+        };
+        self.rx_timeout_timer.set(timer);
+
+        let regs = &*self.registers;
+        regs.rxd_ptr.set(buf.as_ptr() as u32);
+        regs.rxd_maxcnt.write(Count::COUNT.val(buf.len() as u32));
+        self.rx_buffer.replace(buf);
+
+        // With the channels routed as described on
+        // `set_rx_timeout_resources`, TIMER CC fires STOPRX after ~2 idle
+        // byte-times and every RXDRDY restarts the TIMER, so the timeout
+        // only fires once the line goes quiet. Here the channels are only
+        // enabled.
+        timer.set_cc0(idle_timeout_ticks(self.baud_rate.get()));
+        timer.start();
+        self.rxdrdy_ppi_channel.map(|c| c.enable());
+        self.timeout_ppi_channel.map(|c| c.enable());
+
+        regs.intenset.write(Interrupt::ENDRX::SET);
+        regs.task_startrx.write(Task::TASK::SET);
+        Ok(())
+    }
+
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+        if regs.event_endrx.is_set(Event::EVENT) {
+            regs.event_endrx.write(Event::EVENT::CLEAR);
+            let rx_len = regs.rxd_amount.read(Count::COUNT) as usize;
+            self.rxdrdy_ppi_channel.map(|c| c.disable());
+            self.timeout_ppi_channel.map(|c| c.disable());
+            self.rx_timeout_timer.map(|t| t.stop());
+
+            self.rx_buffer.take().map(|buf| {
+                self.idle_client.map(|client| client.received_until_idle(buf, rx_len));
+            });
+        }
+        if regs.event_rxdrdy.is_set(Event::EVENT) {
+            regs.event_rxdrdy.write(Event::EVENT::CLEAR);
+        }
+    }
+
+    /// Services the idle-detection TIMER's own NVIC line. The TIMER's
+    /// COMPARE event fires STOPRX straight from hardware via PPI, but we
+    /// still take the CPU interrupt to clear/restart the TIMER for the
+    /// next `receive_automatic` call.
+    pub fn handle_rx_timeout_interrupt(&self) {
+        self.rx_timeout_timer.map(|t| t.handle_interrupt());
+    }
+}
+
+impl<'a> crate::chip::InterruptHandler for Uarte<'a> {
+    unsafe fn handle_interrupt(&self) {
+        Uarte::handle_interrupt(self)
+    }
+}
+
+/// Adapter registered against the rx-timeout TIMER's own NVIC line; see
+/// `Uarte::handle_rx_timeout_interrupt`.
+pub struct RxTimeoutHandler;
+
+impl crate::chip::InterruptHandler for RxTimeoutHandler {
+    unsafe fn handle_interrupt(&self) {
+        UARTE0.handle_rx_timeout_interrupt();
+    }
+}
+
+pub static UART0_RX_TIMEOUT_HANDLER: RxTimeoutHandler = RxTimeoutHandler;
+
+pub static UARTE0: Uarte = Uarte::new();